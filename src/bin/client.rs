@@ -1,19 +1,39 @@
-use std::{collections::VecDeque, sync::Arc};
+use std::{
+    collections::VecDeque,
+    env, fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
 
 use iced::{
     executor,
     font::Family,
     widget::{self, image},
-    window, Application, Command, Font, Length, Renderer, Settings, Theme,
+    window, Application, Command, Font, Length, Renderer, Settings, Subscription, Theme,
 };
 use iced_aw::helpers;
-use protos::manga::{manga_client::MangaClient, ImageNumber, MangaInfo};
-use tokio::sync::Mutex;
+use protos::manga::{
+    manga_client::MangaClient, ChapterRange, ImageNumber, MangaId, MangaInfo, SearchQuery,
+};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::{sleep, Duration},
+};
 use tonic::{transport::Channel, Request};
 
 pub mod protos;
 
 const BUFFER_LENGTH: usize = 3;
+// 下载工作池大小，限制同时打开的 gRPC 流数量
+const WORKER_COUNT: usize = 5;
+// 指数退避重试参数
+const MAX_RETRIES: u32 = 5;
+const INITIAL_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF_MS: u64 = 30_000;
+// 磁盘缓存最多保留的图片数量
+const CACHE_CAPACITY: usize = 256;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     MangaReader::run(Settings {
@@ -42,30 +62,165 @@ enum ForeAndAft {
 // 标识页面
 #[derive(Debug, Clone)]
 enum Page {
+    Library,
     Info,
     Image,
 }
 
+// 阅读模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReadingMode {
+    ContinuousVertical,
+    SinglePage,
+    DoublePageRTL, // 右到左双页跨页
+}
+
 // 状态修改指令
 #[derive(Debug, Clone)]
 enum Message {
     GetClient(Arc<Mutex<MangaClient<Channel>>>),
+    Search(String),
+    GetResults {
+        query: String,
+        results: Vec<MangaInfo>,
+    },
+    SelectManga(u32),
     GetInfo(MangaInfo),
     GetImage {
         current_number: usize,
+        page: usize,
         fore_and_aft: ForeAndAft,
         image: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+    FetchFailed {
+        current_number: usize,
+        number: u32,
+        fore_and_aft: ForeAndAft,
+        error: String,
     },
     ChangePage(Page),
     ChangeImage(usize),
+    SetReadingMode(ReadingMode),
+    NextPage,
+    PrevPage,
+    StreamImage {
+        number: u32,
+        image: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+    StreamFinished,
+    StreamFailed(String),
+    WindowShifted(Vec<WindowStep>),
+    Ignore,
+}
+
+// 窗口滑动中单页的抓取结果，按真实导航顺序在 WindowShifted 里依次应用
+#[derive(Debug, Clone)]
+struct WindowStep {
+    current_number: usize,
+    page: usize,
+    fore_and_aft: ForeAndAft,
+    outcome: Result<(Vec<u8>, u32, u32), String>,
+}
+
+// 以 (漫画 id, 页码) 的哈希为键的磁盘缓存，带简单的 LRU 淘汰
+struct ImageCache {
+    dir: PathBuf,
+    order: VecDeque<String>,
+    capacity: usize,
+}
+
+impl ImageCache {
+    fn new(capacity: usize) -> Self {
+        let dir = env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".cache/iced_manga_reader");
+        let _ = fs::create_dir_all(&dir);
+        // 用已有文件的修改时间重建跨进程的淘汰顺序，使容量上限对磁盘整体生效；
+        // 修改时间只随写入更新，读取命中不会改变它，所以重建出的是写入顺序
+        let order = Self::load_order(&dir);
+        let mut cache = Self {
+            dir,
+            order,
+            capacity,
+        };
+        cache.evict_excess();
+        cache
+    }
+
+    // 扫描缓存目录，按 mtime 从旧到新排出已有条目
+    fn load_order(dir: &Path) -> VecDeque<String> {
+        let mut entries: Vec<(String, SystemTime)> = fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let key = entry.path().file_stem()?.to_str()?.to_string();
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((key, modified))
+            })
+            .collect();
+        entries.sort_by_key(|&(_, modified)| modified);
+        entries.into_iter().map(|(key, _)| key).collect()
+    }
+
+    fn key(id: u32, number: u32) -> String {
+        format!("{:x}", md5::compute(format!("{id}-{number}")))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.jpg"))
+    }
+
+    // 命中则返回字节并把该键移到最近使用位置
+    fn get(&mut self, id: u32, number: u32) -> Option<Vec<u8>> {
+        let key = Self::key(id, number);
+        let bytes = fs::read(self.path(&key)).ok()?;
+        self.touch(key);
+        Some(bytes)
+    }
+
+    // 写入磁盘、记录访问顺序并淘汰超出容量的旧条目
+    fn put(&mut self, id: u32, number: u32, bytes: &[u8]) {
+        let key = Self::key(id, number);
+        let _ = fs::write(self.path(&key), bytes);
+        self.touch(key);
+        self.evict_excess();
+    }
+
+    fn touch(&mut self, key: String) {
+        self.order.retain(|existing| existing != &key);
+        self.order.push_back(key);
+    }
+
+    fn evict_excess(&mut self) {
+        while self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                let _ = fs::remove_file(self.path(&old));
+            }
+        }
+    }
 }
 
 // 软件状态
 struct MangaReader {
     current_page: Page,
     client: Option<Arc<Mutex<MangaClient<Channel>>>>,
+    semaphore: Arc<Semaphore>,
+    cache: Arc<Mutex<ImageCache>>,
+    current_id: u32,
+    query: String,
+    results: Vec<MangaInfo>,
     info: Option<MangaInfo>,
     image_buffer: VecDeque<Vec<u8>>,
+    // 每页的真实像素尺寸 (宽, 高)，(0, 0) 表示尚未获取
+    page_sizes: Vec<(u32, u32)>,
+    reading_mode: ReadingMode,
+    view_width: f32,
     image_height: u64,
     current_image_number: usize,
     current_number: usize,
@@ -79,10 +234,18 @@ impl Application for MangaReader {
 
     fn new(_flags: Self::Flags) -> (Self, iced::Command<Self::Message>) {
         let reader = Self {
-            current_page: Page::Info,
+            current_page: Page::Library,
             client: None,
+            semaphore: Arc::new(Semaphore::new(WORKER_COUNT)),
+            cache: Arc::new(Mutex::new(ImageCache::new(CACHE_CAPACITY))),
+            current_id: 0,
+            query: String::new(),
+            results: Vec::new(),
             info: None,
             image_buffer: VecDeque::with_capacity(BUFFER_LENGTH),
+            page_sizes: Vec::new(),
+            reading_mode: ReadingMode::ContinuousVertical,
+            view_width: 1300.0,
             image_height: 1764,
             current_image_number: 0,
             current_number: 0,
@@ -105,13 +268,36 @@ impl Application for MangaReader {
         match message {
             Message::GetClient(client) => {
                 self.client = Some(client);
+                // 初始化时列出全部漫画
+                search_manga(Arc::clone(self.client.as_ref().unwrap()), String::new())
+            }
+            Message::Search(query) => {
+                self.query = query.clone();
+                search_manga(Arc::clone(self.client.as_ref().unwrap()), query)
+            }
+            Message::GetResults { query, results } => {
+                // 丢弃不属于最新查询的响应，避免乱序到达的结果覆盖当前搜索
+                if query == self.query {
+                    self.results = results;
+                }
+                Command::none()
+            }
+            Message::SelectManga(id) => {
+                // 重置缓冲区并加载所选漫画的信息与首批图片
+                self.current_id = id;
+                self.info = None;
+                self.page_sizes.clear();
+                self.image_buffer.clear();
+                self.current_image_number = 0;
+                self.current_number = 0;
+                self.current_page = Page::Info;
                 let mut batch = Vec::new();
                 let client_clone = Arc::clone(self.client.as_ref().unwrap());
                 batch.push(Command::perform(
                     async move {
                         let mut client = client_clone.lock().await;
                         client
-                            .get_manga_info(Request::new(protos::manga::Empty {}))
+                            .get_manga_info(Request::new(MangaId { id }))
                             .await
                             .unwrap()
                             .into_inner()
@@ -122,6 +308,9 @@ impl Application for MangaReader {
                     let client_clone = Arc::clone(self.client.as_ref().unwrap());
                     batch.push(get_manga_image(
                         client_clone,
+                        Arc::clone(&self.semaphore),
+                        Arc::clone(&self.cache),
+                        id,
                         0,
                         index as u32,
                         ForeAndAft::Middle,
@@ -130,14 +319,22 @@ impl Application for MangaReader {
                 Command::batch(batch)
             }
             Message::GetInfo(info) => {
+                // 先用占位尺寸填满，真实尺寸随各页抓取到达后再补上
+                self.page_sizes = vec![(0, 0); info.pages as usize];
                 self.info = Some(info);
                 Command::none()
             }
             Message::GetImage {
                 current_number,
+                page,
                 fore_and_aft,
                 image,
+                width,
+                height,
             } => {
+                if let Some(size) = self.page_sizes.get_mut(page) {
+                    *size = (width, height);
+                }
                 match fore_and_aft {
                     ForeAndAft::Fore => {
                         self.image_buffer.pop_back();
@@ -154,72 +351,429 @@ impl Application for MangaReader {
                 self.current_image_number = current_number;
                 Command::none()
             }
+            Message::FetchFailed {
+                current_number,
+                number,
+                fore_and_aft,
+                error,
+            } => {
+                eprintln!("failed to fetch page {number}: {error}");
+                // 用占位符撑住缓冲窗口，避免整个阅读器崩溃，移动方式与 GetImage 保持一致
+                match fore_and_aft {
+                    ForeAndAft::Fore => {
+                        self.image_buffer.pop_back();
+                        self.image_buffer.push_front(Vec::new());
+                    }
+                    ForeAndAft::Middle => {
+                        self.image_buffer.push_back(Vec::new());
+                    }
+                    ForeAndAft::Aft => {
+                        self.image_buffer.pop_front();
+                        self.image_buffer.push_back(Vec::new());
+                    }
+                }
+                self.current_image_number = current_number;
+                Command::none()
+            }
             Message::ChangePage(page) => {
                 self.current_page = page;
                 Command::none()
             }
+            Message::SetReadingMode(mode) => {
+                self.reading_mode = mode;
+                Command::none()
+            }
+            Message::NextPage => {
+                // 双页模式一次翻两页，其余翻一页；越界则停在最后一页
+                let pages = self.info.as_ref().unwrap().pages as usize;
+                let step = if self.reading_mode == ReadingMode::DoublePageRTL {
+                    2
+                } else {
+                    1
+                };
+                let number = (self.current_number + step).min(pages - 1);
+                self.update(Message::ChangeImage(number))
+            }
+            Message::PrevPage => {
+                let step = if self.reading_mode == ReadingMode::DoublePageRTL {
+                    2
+                } else {
+                    1
+                };
+                let number = self.current_number.saturating_sub(step);
+                self.update(Message::ChangeImage(number))
+            }
+            Message::StreamImage {
+                number,
+                image,
+                width,
+                height,
+            } => {
+                // 流式到达的页：记录尺寸并写入磁盘缓存，供窗口抓取命中
+                if let Some(size) = self.page_sizes.get_mut(number as usize) {
+                    *size = (width, height);
+                }
+                // 若这一页恰好落在当前缓冲窗口内，直接替换进 image_buffer 让画面提前更新
+                if let Some(info) = &self.info {
+                    let pages = info.pages as usize;
+                    let start = window_start(self.current_image_number, pages);
+                    let page = number as usize;
+                    if self.image_buffer.len() == BUFFER_LENGTH
+                        && (start..start + BUFFER_LENGTH).contains(&page)
+                    {
+                        if let Some(slot) = self.image_buffer.get_mut(page - start) {
+                            *slot = image.clone();
+                        }
+                    }
+                }
+                let cache = Arc::clone(&self.cache);
+                let id = self.current_id;
+                Command::perform(
+                    async move {
+                        cache.lock().await.put(id, number, &image);
+                    },
+                    |_| Message::Ignore,
+                )
+            }
+            Message::StreamFailed(error) => {
+                eprintln!("chapter stream failed: {error}");
+                Command::none()
+            }
+            Message::StreamFinished | Message::Ignore => Command::none(),
             Message::ChangeImage(number) => {
                 if number == self.current_number {
                     return Command::none();
                 }
                 self.current_number = number;
                 let pages = self.info.as_ref().unwrap().pages as usize;
-                let client = Arc::clone(self.client.as_ref().unwrap());
-                if number > self.current_image_number && (2..(pages - 1)).contains(&number) {
-                    get_manga_image(
-                        client,
-                        number,
-                        (number + BUFFER_LENGTH / 2) as u32,
-                        ForeAndAft::Aft,
-                    )
-                } else if number < self.current_image_number && (1..(pages - 2)).contains(&number) {
-                    get_manga_image(
-                        client,
-                        number,
-                        (number - BUFFER_LENGTH / 2) as u32,
-                        ForeAndAft::Fore,
-                    )
+                // 按实际跨越的页数逐页列出窗口前沿/后沿需要补齐的步骤，而不是假定每次只移动一页
+                // （双页模式一次跳两页时，缺的这一步会让 image_buffer 与 current_image_number 错位）
+                let mut steps = Vec::new();
+                if number > self.current_image_number {
+                    for target in (self.current_image_number + 1)..=number {
+                        if !(2..(pages - 1)).contains(&target) {
+                            continue;
+                        }
+                        steps.push((target, (target + BUFFER_LENGTH / 2) as u32, ForeAndAft::Aft));
+                    }
                 } else {
-                    Command::none()
+                    for target in (number..self.current_image_number).rev() {
+                        if !(1..(pages - 2)).contains(&target) {
+                            continue;
+                        }
+                        steps.push((target, (target - BUFFER_LENGTH / 2) as u32, ForeAndAft::Fore));
+                    }
                 }
+                if steps.is_empty() {
+                    return Command::none();
+                }
+                // 逐步按导航顺序依次抓取（而非并发），避免响应乱序到达时图片被挪到错误的槽位
+                shift_window(
+                    Arc::clone(self.client.as_ref().unwrap()),
+                    Arc::clone(&self.semaphore),
+                    Arc::clone(&self.cache),
+                    self.current_id,
+                    steps,
+                )
+            }
+            Message::WindowShifted(steps) => {
+                // 按抓取顺序依次应用，保证 current_image_number 最终落在真正抓到的那一页
+                for step in steps {
+                    match step.outcome {
+                        Ok((image, width, height)) => {
+                            if let Some(size) = self.page_sizes.get_mut(step.page) {
+                                *size = (width, height);
+                            }
+                            match step.fore_and_aft {
+                                ForeAndAft::Fore => {
+                                    self.image_buffer.pop_back();
+                                    self.image_buffer.push_front(image);
+                                }
+                                ForeAndAft::Middle => {
+                                    self.image_buffer.push_back(image);
+                                }
+                                ForeAndAft::Aft => {
+                                    self.image_buffer.pop_front();
+                                    self.image_buffer.push_back(image);
+                                }
+                            }
+                        }
+                        Err(error) => {
+                            eprintln!("failed to fetch page {}: {error}", step.page);
+                            match step.fore_and_aft {
+                                ForeAndAft::Fore => {
+                                    self.image_buffer.pop_back();
+                                    self.image_buffer.push_front(Vec::new());
+                                }
+                                ForeAndAft::Middle => {
+                                    self.image_buffer.push_back(Vec::new());
+                                }
+                                ForeAndAft::Aft => {
+                                    self.image_buffer.pop_front();
+                                    self.image_buffer.push_back(Vec::new());
+                                }
+                            }
+                        }
+                    }
+                    self.current_image_number = step.current_number;
+                }
+                Command::none()
             }
         }
     }
 
     fn view(&self) -> iced::Element<'_, Self::Message, iced::Renderer<Self::Theme>> {
-        if let Page::Info = self.current_page {
-            info_page(self)
-        } else {
-            image_page(self)
+        match self.current_page {
+            Page::Library => library_page(self),
+            Page::Info => info_page(self),
+            Page::Image => image_page(self),
         }
         .into()
     }
+
+    fn subscription(&self) -> Subscription<Self::Message> {
+        // 阅读时在后台按页流式拉取整章，边到边填入缓存
+        match (&self.current_page, &self.client, &self.info) {
+            (Page::Image, Some(client), Some(info)) => {
+                stream_chapter(Arc::clone(client), self.current_id, info.pages)
+            }
+            _ => Subscription::none(),
+        }
+    }
 }
 
-fn get_manga_image(
+// 流式拉取章节的订阅状态机
+enum StreamState {
+    Connecting(Arc<Mutex<MangaClient<Channel>>>, u32, u32),
+    Streaming(tonic::Streaming<protos::manga::Image>),
+    Finished,
+}
+
+fn stream_chapter(
     client: Arc<Mutex<MangaClient<Channel>>>,
-    current_number: usize,
-    number: u32,
-    fore_and_aft: ForeAndAft,
+    id: u32,
+    pages: u32,
+) -> Subscription<Message> {
+    // 以类型作为订阅 id，保证同一章节只建立一条流
+    struct Chapter;
+    iced::subscription::unfold(
+        std::any::TypeId::of::<Chapter>(),
+        StreamState::Connecting(client, id, pages),
+        move |state| async move {
+            match state {
+                StreamState::Connecting(client, id, pages) => {
+                    let stream = {
+                        let mut client = client.lock().await;
+                        client
+                            .stream_chapter(Request::new(ChapterRange {
+                                id,
+                                start: 0,
+                                end: pages.saturating_sub(1),
+                            }))
+                            .await
+                    };
+                    match stream {
+                        Ok(response) => read_next(response.into_inner()).await,
+                        Err(status) => (
+                            Message::StreamFailed(status.to_string()),
+                            StreamState::Finished,
+                        ),
+                    }
+                }
+                StreamState::Streaming(stream) => read_next(stream).await,
+                // 流已结束，挂起订阅直到其被丢弃
+                StreamState::Finished => iced::futures::future::pending().await,
+            }
+        },
+    )
+}
+
+// 读取流中的下一页并映射成对应消息
+async fn read_next(mut stream: tonic::Streaming<protos::manga::Image>) -> (Message, StreamState) {
+    match stream.message().await {
+        Ok(Some(image)) => (
+            Message::StreamImage {
+                number: image.number,
+                image: image.image,
+                width: image.width,
+                height: image.height,
+            },
+            StreamState::Streaming(stream),
+        ),
+        Ok(None) => (Message::StreamFinished, StreamState::Finished),
+        Err(status) => (
+            Message::StreamFailed(status.to_string()),
+            StreamState::Finished,
+        ),
+    }
+}
+
+fn search_manga(
+    client: Arc<Mutex<MangaClient<Channel>>>,
+    query: String,
 ) -> Command<Message> {
     Command::perform(
         async move {
             let mut client = client.lock().await;
-            client
-                .get_manga_image(Request::new(ImageNumber { number }))
+            let results = client
+                .search_manga(Request::new(SearchQuery {
+                    query: query.clone(),
+                    tags: Vec::new(),
+                    artists: Vec::new(),
+                }))
                 .await
                 .unwrap()
                 .into_inner()
-                .image
+                .mangas;
+            (query, results)
         },
-        move |image| Message::GetImage {
-            current_number,
-            fore_and_aft,
-            image,
+        |(query, results)| Message::GetResults { query, results },
+    )
+}
+
+// 取单页：磁盘缓存命中则直接返回，否则经工作池限流后走退避重试，成功后落盘
+async fn fetch_page(
+    client: Arc<Mutex<MangaClient<Channel>>>,
+    semaphore: Arc<Semaphore>,
+    cache: Arc<Mutex<ImageCache>>,
+    id: u32,
+    number: u32,
+) -> Result<(Vec<u8>, u32, u32), String> {
+    if let Some(image) = cache.lock().await.get(id, number) {
+        let (width, height) = image_dimensions(&image);
+        return Ok((image, width, height));
+    }
+    let _permit = semaphore.acquire_owned().await.unwrap();
+    let result = fetch_with_backoff(client, id, number).await;
+    if let Ok((image, _, _)) = &result {
+        cache.lock().await.put(id, number, image);
+    }
+    result
+}
+
+fn get_manga_image(
+    client: Arc<Mutex<MangaClient<Channel>>>,
+    semaphore: Arc<Semaphore>,
+    cache: Arc<Mutex<ImageCache>>,
+    id: u32,
+    current_number: usize,
+    number: u32,
+    fore_and_aft: ForeAndAft,
+) -> Command<Message> {
+    Command::perform(
+        fetch_page(client, semaphore, cache, id, number),
+        move |result| match result {
+            Ok((image, width, height)) => Message::GetImage {
+                current_number,
+                page: number as usize,
+                fore_and_aft,
+                image,
+                width,
+                height,
+            },
+            Err(error) => Message::FetchFailed {
+                current_number,
+                number,
+                fore_and_aft,
+                error,
+            },
         },
     )
 }
 
+// 一次窗口滑动里要跨越的所有页，按真实导航顺序依次（而非并发）抓取，
+// 避免多页跳转时各页请求互相抢跑，导致缓冲区错位或图片错位
+fn shift_window(
+    client: Arc<Mutex<MangaClient<Channel>>>,
+    semaphore: Arc<Semaphore>,
+    cache: Arc<Mutex<ImageCache>>,
+    id: u32,
+    steps: Vec<(usize, u32, ForeAndAft)>,
+) -> Command<Message> {
+    Command::perform(
+        async move {
+            let mut results = Vec::with_capacity(steps.len());
+            for (current_number, number, fore_and_aft) in steps {
+                let outcome = fetch_page(
+                    Arc::clone(&client),
+                    Arc::clone(&semaphore),
+                    Arc::clone(&cache),
+                    id,
+                    number,
+                )
+                .await;
+                results.push(WindowStep {
+                    current_number,
+                    page: number as usize,
+                    fore_and_aft,
+                    outcome,
+                });
+            }
+            results
+        },
+        Message::WindowShifted,
+    )
+}
+
+// 失败时按指数退避重试有限次，仍失败则返回错误文本交给 UI 处理
+async fn fetch_with_backoff(
+    client: Arc<Mutex<MangaClient<Channel>>>,
+    id: u32,
+    number: u32,
+) -> Result<(Vec<u8>, u32, u32), String> {
+    let mut backoff = INITIAL_BACKOFF_MS;
+    for attempt in 0..=MAX_RETRIES {
+        let result = {
+            let mut client = client.lock().await;
+            client
+                .get_manga_image(Request::new(ImageNumber { id, number }))
+                .await
+        };
+        match result {
+            Ok(response) => {
+                let image = response.into_inner();
+                return Ok((image.image, image.width, image.height));
+            }
+            Err(status) if attempt == MAX_RETRIES => return Err(status.to_string()),
+            Err(_) => {
+                sleep(Duration::from_millis(backoff)).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF_MS);
+            }
+        }
+    }
+    unreachable!()
+}
+
+// 通过文件头廉价地读取图片像素尺寸，失败时返回 (0, 0)
+fn image_dimensions(bytes: &[u8]) -> (u32, u32) {
+    image::io::Reader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+        .unwrap_or((0, 0))
+}
+
+fn library_page(state: &MangaReader) -> widget::Container<Message, Renderer> {
+    let mut grid = iced_aw::Grid::with_columns(4);
+    for manga in &state.results {
+        grid.insert(
+            widget::button(widget::column!(
+                widget::image(image::Handle::from_memory(manga.cover.clone()))
+                    .width(Length::Fixed(200.0)),
+                widget::text(&manga.english_name),
+            ))
+            .on_press(Message::SelectManga(manga.id)),
+        );
+    }
+    widget::container(widget::column!(
+        widget::text_input("Search manga", &state.query).on_input(Message::Search),
+        widget::scrollable(grid),
+    ))
+    .center_x()
+    .width(Length::Fill)
+    .height(Length::Fill)
+}
+
 fn info_page(state: &MangaReader) -> widget::Container<Message, Renderer> {
     if let Some(manga) = &state.info {
         widget::container(widget::row!(
@@ -255,8 +809,12 @@ fn info_page(state: &MangaReader) -> widget::Container<Message, Renderer> {
                     grid.insert(widget::text(&manga.uploaded));
                     grid
                 },
-                widget::button(widget::text("Read Manga"))
-                    .on_press(Message::ChangePage(Page::Image))
+                widget::row!(
+                    widget::button(widget::text("Back Library"))
+                        .on_press(Message::ChangePage(Page::Library)),
+                    widget::button(widget::text("Read Manga"))
+                        .on_press(Message::ChangePage(Page::Image)),
+                )
             )
         ))
         .center_x()
@@ -269,83 +827,176 @@ fn info_page(state: &MangaReader) -> widget::Container<Message, Renderer> {
 }
 
 fn image_page(state: &MangaReader) -> widget::Container<Message, Renderer> {
-    widget::container(widget::column!(
-        widget::button(widget::text("Back Info")).on_press(Message::ChangePage(Page::Info)),
-        widget::scrollable(widget::column({
-            println!(
-                "current number = {}, buffer length = {}",
-                state.current_image_number,
-                state.image_buffer.len()
-            );
-            if state.image_buffer.len() < BUFFER_LENGTH {
-                vec![widget::Space::new(Length::Shrink, Length::Shrink).into()]
+    // 每页按真实宽高比在 view_width 宽度下缩放出的高度，未知尺寸回退到默认高度；
+    // view_width 固定为初始值 1300，窗口尺寸变化时不会更新
+    let heights: Vec<f32> = state
+        .page_sizes
+        .iter()
+        .map(|&(width, height)| {
+            if width > 0 {
+                height as f32 * state.view_width / width as f32
             } else {
-                let pages = state.info.as_ref().unwrap().pages as usize;
-                let mut list = Vec::with_capacity(pages);
-                match state.current_image_number {
-                    number if (0..(BUFFER_LENGTH / 2)).contains(&number) => {
-                        for index in 0..BUFFER_LENGTH {
-                            list.push(
-                                make_image(&state.image_buffer, index, state.image_height).into(),
-                            );
-                        }
-                        for _ in BUFFER_LENGTH..pages {
-                            list.push(make_space(state.image_height).into());
-                        }
-                    }
-                    number if ((pages - (BUFFER_LENGTH / 2))..pages).contains(&number) => {
-                        for _ in 0..(pages - BUFFER_LENGTH) {
-                            list.push(make_space(state.image_height).into());
-                        }
-                        for index in 0..BUFFER_LENGTH {
-                            list.push(
-                                make_image(&state.image_buffer, index, state.image_height).into(),
-                            );
-                        }
-                    }
-                    number => {
-                        for _ in 0..(number - (BUFFER_LENGTH / 2)) {
-                            list.push(make_space(state.image_height).into());
-                        }
-                        for index in 0..BUFFER_LENGTH {
-                            list.push(
-                                make_image(&state.image_buffer, index, state.image_height).into(),
-                            );
-                        }
-                        for _ in (number + (BUFFER_LENGTH / 2 + 1))..pages {
-                            list.push(make_space(state.image_height).into());
-                        }
-                    }
-                }
-                list
+                state.image_height as f32
             }
-        }))
-        .on_scroll(|viewport| {
-            //println!("{viewport:?}");
-            let y = viewport.absolute_offset().y as usize;
-            let current_number = if y == 0 {
-                0
-            } else {
-                y / state.image_height as usize
-            };
-            Message::ChangeImage(current_number)
         })
-    ))
-    .center_x()
-    .width(Length::Fill)
+        .collect();
+
+    // 顶部控制条：返回、模式切换，分页模式下再加上翻页按钮
+    let mode_button = |label, mode| {
+        let button = widget::button(widget::text(label));
+        if state.reading_mode == mode {
+            button
+        } else {
+            button.on_press(Message::SetReadingMode(mode))
+        }
+    };
+    let mut controls = widget::row!(
+        widget::button(widget::text("Back Info")).on_press(Message::ChangePage(Page::Info)),
+        mode_button("Vertical", ReadingMode::ContinuousVertical),
+        mode_button("Single", ReadingMode::SinglePage),
+        mode_button("Double RTL", ReadingMode::DoublePageRTL),
+    );
+    if state.reading_mode != ReadingMode::ContinuousVertical {
+        controls = controls.push(
+            widget::button(widget::text("Prev")).on_press(Message::PrevPage),
+        );
+        controls = controls.push(
+            widget::button(widget::text("Next")).on_press(Message::NextPage),
+        );
+    }
+
+    let content = match state.reading_mode {
+        ReadingMode::ContinuousVertical => continuous_view(state, heights),
+        ReadingMode::SinglePage => paged_view(state, &heights, false),
+        ReadingMode::DoublePageRTL => paged_view(state, &heights, true),
+    };
+
+    widget::container(widget::column!(controls, content))
+        .center_x()
+        .width(Length::Fill)
+}
+
+// 连续竖向滚动布局：窗口内显示图片，其余页用占位高度撑开
+fn continuous_view(
+    state: &MangaReader,
+    heights: Vec<f32>,
+) -> iced::Element<'static, Message, Renderer> {
+    // on_scroll 闭包需要独立持有一份高度表来定位页码
+    let scroll_heights = heights.clone();
+    widget::scrollable(widget::column({
+        println!(
+            "current number = {}, buffer length = {}",
+            state.current_image_number,
+            state.image_buffer.len()
+        );
+        if state.image_buffer.len() < BUFFER_LENGTH {
+            vec![widget::Space::new(Length::Shrink, Length::Shrink).into()]
+        } else {
+            let pages = state.info.as_ref().unwrap().pages as usize;
+            let start = window_start(state.current_image_number, pages);
+            let mut list = Vec::with_capacity(pages);
+            for page in 0..pages {
+                if (start..start + BUFFER_LENGTH).contains(&page) {
+                    list.push(make_page(&state.image_buffer, page - start, heights[page]));
+                } else {
+                    list.push(make_space(heights[page]).into());
+                }
+            }
+            list
+        }
+    }))
+    .on_scroll(move |viewport| {
+        //println!("{viewport:?}");
+        let y = viewport.absolute_offset().y;
+        Message::ChangeImage(page_at_offset(&scroll_heights, y))
+    })
+    .into()
+}
+
+// 分页布局：单页显示当前页，双页（RTL）把高页号放在左侧
+fn paged_view(
+    state: &MangaReader,
+    heights: &[f32],
+    double: bool,
+) -> iced::Element<'static, Message, Renderer> {
+    if state.image_buffer.len() < BUFFER_LENGTH {
+        return widget::Space::new(Length::Shrink, Length::Shrink).into();
+    }
+    let pages = state.info.as_ref().unwrap().pages as usize;
+    let current = state.current_image_number;
+    let start = window_start(current, pages);
+    let page_of = |page: usize, height: f32| make_page(&state.image_buffer, page - start, height);
+
+    if double {
+        let left = current + 1;
+        // 奇数结尾时最后一页单独显示，否则高页号在左、当前页在右
+        if left < pages && (start..start + BUFFER_LENGTH).contains(&left) {
+            widget::row!(
+                page_of(left, heights[left]),
+                page_of(current, heights[current]),
+            )
+            .into()
+        } else {
+            page_of(current, heights[current])
+        }
+    } else {
+        page_of(current, heights[current])
+    }
+}
+
+// 缓冲窗口在整本漫画中的起始页，夹在两端边界内
+fn window_start(current: usize, pages: usize) -> usize {
+    // 页数不足 BUFFER_LENGTH 的漫画（单页、双页）窗口要收缩到 pages，否则下面的减法会下溢
+    let window = pages.min(BUFFER_LENGTH);
+    let half = window / 2;
+    match current {
+        number if number < half => 0,
+        number if number >= pages - half => pages - window,
+        number => number - half,
+    }
+}
+
+fn make_page(
+    buffer: &VecDeque<Vec<u8>>,
+    index: usize,
+    height: f32,
+) -> iced::Element<'static, Message, Renderer> {
+    // 空字节表示这一页抓取失败，原地显示占位符而不是图片
+    if buffer.get(index).unwrap().is_empty() {
+        widget::container(widget::text("Failed to load page"))
+            .center_x()
+            .center_y()
+            .width(Length::Fill)
+            .height(Length::Fixed(height))
+            .into()
+    } else {
+        make_image(buffer, index, height).into()
+    }
+}
+
+// 累加各页高度，定位滚动偏移 y 落在的页码
+fn page_at_offset(heights: &[f32], y: f32) -> usize {
+    let mut accumulated = 0.0;
+    for (page, height) in heights.iter().enumerate() {
+        accumulated += height;
+        if y < accumulated {
+            return page;
+        }
+    }
+    heights.len().saturating_sub(1)
 }
 
-fn make_space(height: u64) -> widget::Space {
-    widget::Space::new(Length::Shrink, Length::Fixed(height as f32))
+fn make_space(height: f32) -> widget::Space {
+    widget::Space::new(Length::Shrink, Length::Fixed(height))
 }
 
 fn make_image(
     buffer: &VecDeque<Vec<u8>>,
     index: usize,
-    height: u64,
+    height: f32,
 ) -> widget::Image<image::Handle> {
     widget::image(image::Handle::from_memory(
         buffer.get(index).unwrap().clone(),
     ))
-    .height(Length::Fixed(height as f32))
+    .height(Length::Fixed(height))
 }
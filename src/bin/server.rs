@@ -1,11 +1,15 @@
 use std::{
     fs::{self, File},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use protos::manga::{manga_server::Manga, Empty, Image, ImageNumber, MangaInfo};
+use protos::manga::{
+    manga_server::Manga, ChapterRange, Image, ImageNumber, MangaId, MangaInfo, MangaList,
+    SearchQuery,
+};
 use serde::{Deserialize, Serialize};
-use tokio::signal;
+use tokio::{signal, sync::mpsc};
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::{transport::Server, Request, Response, Status};
 
 use crate::protos::manga::manga_server::MangaServer;
@@ -13,6 +17,10 @@ use crate::protos::manga::manga_server::MangaServer;
 pub mod protos;
 
 const ADDRESS: &str = "[::1]:8080";
+// 所有漫画按 manga/<id>/manga.json 组织
+const MANGA_ROOT: &str = "manga";
+// 章节流响应通道的缓冲页数
+const STREAM_BUFFER: usize = 8;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct MangaJson {
@@ -43,39 +51,135 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 struct MangaService;
 
+// 读取 manga/<id>/manga.json 并附带封面，组装成一条 MangaInfo
+fn read_manga(id: u32) -> Result<MangaInfo, Status> {
+    let dir = Path::new(MANGA_ROOT).join(id.to_string());
+    let manga_path =
+        File::open(dir.join("manga.json")).map_err(|err| Status::not_found(err.to_string()))?;
+    let manga_json: MangaJson =
+        serde_json::from_reader(manga_path).map_err(|err| Status::unknown(err.to_string()))?;
+    let manga_cover =
+        fs::read(dir.join("cover.jpg")).map_err(|err| Status::not_found(err.to_string()))?;
+    Ok(MangaInfo {
+        id: manga_json.id,
+        english_name: manga_json.english_name,
+        japanese_name: manga_json.japanese_name,
+        cover: manga_cover,
+        tags: manga_json.tags,
+        artists: manga_json.artists,
+        pages: manga_json.pages,
+        uploaded: manga_json.uploaded,
+    })
+}
+
+// 读取某一页图片，并通过文件头附带真实像素尺寸与页码
+fn read_image(id: u32, number: u32) -> Result<Image, Status> {
+    let image_path = Path::new(MANGA_ROOT)
+        .join(id.to_string())
+        .join("images")
+        .join(format!("{number}.jpg"));
+    let (width, height) =
+        image::image_dimensions(&image_path).map_err(|err| Status::unknown(err.to_string()))?;
+    let image = fs::read(&image_path).map_err(|err| Status::not_found(err.to_string()))?;
+    Ok(Image {
+        image,
+        width,
+        height,
+        number,
+    })
+}
+
 #[tonic::async_trait]
 impl Manga for MangaService {
     async fn get_manga_info(
         &self,
-        _request: Request<Empty>,
+        request: Request<MangaId>,
     ) -> Result<Response<MangaInfo>, Status> {
-        let manga_path =
-            File::open("assets/manga.json").map_err(|err| Status::not_found(err.to_string()))?;
-        let manga_json: MangaJson =
-            serde_json::from_reader(manga_path).map_err(|err| Status::unknown(err.to_string()))?;
-        let manga_cover =
-            fs::read("assets/cover.jpg").map_err(|err| Status::not_found(err.to_string()))?;
-        let manga_info = MangaInfo {
-            id: manga_json.id,
-            english_name: manga_json.english_name,
-            japanese_name: manga_json.japanese_name,
-            cover: manga_cover,
-            tags: manga_json.tags,
-            artists: manga_json.artists,
-            pages: manga_json.pages,
-            uploaded: manga_json.uploaded,
-        };
-        Ok(Response::new(manga_info))
+        let id = request.into_inner().id;
+        Ok(Response::new(read_manga(id)?))
+    }
+
+    async fn search_manga(
+        &self,
+        request: Request<SearchQuery>,
+    ) -> Result<Response<MangaList>, Status> {
+        let SearchQuery {
+            query,
+            tags,
+            artists,
+        } = request.into_inner();
+        let query = query.to_lowercase();
+
+        let mut mangas = Vec::new();
+        let entries =
+            fs::read_dir(MANGA_ROOT).map_err(|err| Status::not_found(err.to_string()))?;
+        for entry in entries {
+            let entry = entry.map_err(|err| Status::unknown(err.to_string()))?;
+            let id: u32 = match entry.file_name().to_string_lossy().parse() {
+                Ok(id) => id,
+                Err(_) => continue,
+            };
+            let manga = read_manga(id)?;
+
+            // 名字、标签、作者的大小写无关子串匹配
+            let matches_query = query.is_empty()
+                || manga.english_name.to_lowercase().contains(&query)
+                || manga.japanese_name.to_lowercase().contains(&query)
+                || manga
+                    .tags
+                    .iter()
+                    .any(|tag| tag.to_lowercase().contains(&query))
+                || manga
+                    .artists
+                    .iter()
+                    .any(|artist| artist.to_lowercase().contains(&query));
+            let matches_tags = tags.iter().all(|wanted| {
+                let wanted = wanted.to_lowercase();
+                manga.tags.iter().any(|tag| tag.to_lowercase() == wanted)
+            });
+            let matches_artists = artists.iter().all(|wanted| {
+                let wanted = wanted.to_lowercase();
+                manga
+                    .artists
+                    .iter()
+                    .any(|artist| artist.to_lowercase() == wanted)
+            });
+
+            if matches_query && matches_tags && matches_artists {
+                mangas.push(manga);
+            }
+        }
+        mangas.sort_by_key(|manga| manga.id);
+        Ok(Response::new(MangaList { mangas }))
     }
 
     async fn get_manga_image(
         &self,
         request: Request<ImageNumber>,
     ) -> Result<Response<Image>, Status> {
-        let image_number = request.into_inner().number;
-        println!("image number = {image_number}");
-        let image_path = PathBuf::from(format!("assets/images/{image_number}.jpg"));
-        let image = fs::read(image_path)?;
-        Ok(Response::new(Image { image }))
+        let ImageNumber { id, number } = request.into_inner();
+        println!("manga id = {id}, image number = {number}");
+        Ok(Response::new(read_image(id, number)?))
+    }
+
+    type StreamChapterStream = ReceiverStream<Result<Image, Status>>;
+
+    async fn stream_chapter(
+        &self,
+        request: Request<ChapterRange>,
+    ) -> Result<Response<Self::StreamChapterStream>, Status> {
+        let ChapterRange { id, start, end } = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+        // 顺序读取整个区间并逐页推入响应流，客户端消费端断开时提前结束
+        tokio::spawn(async move {
+            for number in start..=end {
+                let message = read_image(id, number);
+                let stop = message.is_err();
+                if tx.send(message).await.is_err() || stop {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
     }
 }